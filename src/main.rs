@@ -1,10 +1,11 @@
 use anyhow::{bail, Result};
 use clap::{Parser, Subcommand};
-use timeclock::{Debug, Timeclock};
+use timeclock::{Debug, Format, Output, Timeclock};
 
 mod timeclock;
 
-const TIMESHEET_PATH: &str = "timesheet.json";
+/// Where the timesheet was kept before profiles were supported.
+const LEGACY_TIMESHEET_PATH: &str = "timesheet.json";
 
 #[derive(Parser)]
 struct Cli {
@@ -13,6 +14,15 @@ struct Cli {
 
     #[arg(short, long)]
     debug: bool,
+
+    #[arg(long, global = true, default_value = timeclock::DEFAULT_PROFILE)]
+    profile: String,
+
+    #[arg(long, global = true)]
+    iso: bool,
+
+    #[arg(long, global = true, value_name = "HOURS", value_parser = parse_hours)]
+    max_session: Option<chrono::TimeDelta>,
 }
 
 #[derive(Subcommand)]
@@ -21,11 +31,33 @@ enum Commands {
     In {
         #[arg(short, long, default_value = None)]
         at: Option<chrono::NaiveDateTime>,
+
+        #[arg(long)]
+        json: bool,
     },
     #[clap(about = "Clock out")]
     Out {
         #[arg(short, long, default_value = None)]
         at: Option<chrono::NaiveDateTime>,
+
+        #[arg(long)]
+        json: bool,
+    },
+    #[clap(about = "Prints the time clocked in each week of a month")]
+    Report {
+        #[arg(short, long, value_parser = parse_month)]
+        month: Option<chrono::NaiveDate>,
+    },
+    #[clap(about = "Get the current and longest streaks of consecutive days worked")]
+    Streak,
+    #[clap(about = "Lists clock ins that are missing a clock out")]
+    Gaps,
+    #[clap(about = "Replaces the timesheet with `action,timestamp` rows from a CSV file")]
+    Import {
+        file: std::path::PathBuf,
+
+        #[arg(long)]
+        force: bool,
     },
     #[clap(about = "Get the raw timesheet")]
     Raw,
@@ -36,10 +68,26 @@ enum Commands {
         #[clap(subcommand)]
         granularity: Granularity,
     },
+    #[clap(about = "Get the average time clocked per day worked.")]
+    Average {
+        #[clap(subcommand)]
+        granularity: Granularity,
+    },
+    #[clap(about = "Get the pay earned for time clocked at an hourly rate.")]
+    Pay {
+        #[clap(subcommand)]
+        granularity: Granularity,
+
+        #[arg(short, long, value_parser = parse_rate)]
+        rate: f64,
+    },
     #[clap(about = "Prints out the timesheet as a table")]
     Timesheet {
         #[arg(short, long, default_value = None)]
         on: Option<chrono::NaiveDate>,
+
+        #[arg(long)]
+        detailed: bool,
     },
     #[clap(about = "Watches for the specified number of hours worked this week")]
     Watch {
@@ -48,6 +96,8 @@ enum Commands {
     },
     #[clap(about = "Returns the path to the timesheet file")]
     File,
+    #[clap(about = "Lists the profiles that have a timesheet")]
+    Profiles,
 }
 
 /// Time clocked _this_ period.
@@ -61,6 +111,38 @@ pub enum Granularity {
     Month,
     #[clap(about = "Time clocked this year")]
     Year,
+    #[clap(about = "Time clocked between two dates, inclusive")]
+    Range {
+        #[arg(long)]
+        from: chrono::NaiveDate,
+
+        #[arg(long)]
+        to: chrono::NaiveDate,
+    },
+}
+
+/// Parses a positive, possibly fractional, number of hours.
+fn parse_hours(hours: &str) -> Result<chrono::TimeDelta, String> {
+    hours
+        .parse::<f64>()
+        .ok()
+        .filter(|hours| hours.is_finite() && *hours > 0.0)
+        .and_then(|hours| chrono::TimeDelta::try_seconds((hours * 3600.0) as i64))
+        .ok_or_else(|| format!("expected a positive number of hours, got `{}`", hours))
+}
+
+/// Parses a non-negative hourly rate.
+fn parse_rate(rate: &str) -> Result<f64, String> {
+    rate.parse::<f64>()
+        .ok()
+        .filter(|rate| rate.is_finite() && *rate >= 0.0)
+        .ok_or_else(|| format!("expected a non-negative hourly rate, got `{}`", rate))
+}
+
+/// Parses a `YYYY-MM` month into its first day.
+fn parse_month(month: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_err(|_| format!("expected a month as YYYY-MM, got `{}`", month))
 }
 
 impl From<&Granularity> for timeclock::This {
@@ -70,33 +152,85 @@ impl From<&Granularity> for timeclock::This {
             Granularity::Week => timeclock::This::Week,
             Granularity::Month => timeclock::This::Month,
             Granularity::Year => timeclock::This::Year,
+            Granularity::Range { from, to } => timeclock::This::Range(*from, *to),
         }
     }
 }
 
+/// Moves a timesheet from before profiles were supported into the default
+/// profile, unless the default profile already has one.
+fn adopt_legacy_timesheet(
+    legacy_path: &std::path::Path,
+    timesheet_path: &std::path::Path,
+) -> Result<()> {
+    if !legacy_path.exists() || timesheet_path.exists() {
+        return Ok(());
+    }
+
+    if let Some(profiles_dir) = timesheet_path.parent() {
+        std::fs::create_dir_all(profiles_dir)?;
+    }
+    std::fs::rename(legacy_path, timesheet_path)?;
+    eprintln!(
+        "Moved timesheet from {} to {}",
+        legacy_path.display(),
+        timesheet_path.display()
+    );
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    let timesheet_path = dirs::data_dir().map(|data_dir| data_dir.join(TIMESHEET_PATH));
-    if timesheet_path.is_none() {
+    let data_dir = dirs::data_dir();
+    if data_dir.is_none() {
         bail!("Unable to locate timesheet.");
     }
 
-    let timesheet_path = timesheet_path.unwrap();
+    let data_dir = data_dir.unwrap();
+    let timesheet_path = timeclock::profile_path(&data_dir, &cli.profile)?;
+    if cli.profile == timeclock::DEFAULT_PROFILE {
+        adopt_legacy_timesheet(&data_dir.join(LEGACY_TIMESHEET_PATH), &timesheet_path)?;
+    }
+
     let clock = Timeclock::new(
         &timesheet_path,
         if cli.debug { Debug::On } else { Debug::Off },
-    );
+    )
+    .with_format(if cli.iso {
+        Format::Iso8601
+    } else {
+        Format::Hms
+    })
+    .with_output(match cli.command {
+        Commands::In { json: true, .. } | Commands::Out { json: true, .. } => Output::Json,
+        _ => Output::Text,
+    })
+    .with_max_session(cli.max_session);
 
     match &cli.command {
-        Commands::In { at } => clock.clock_in(*at)?,
-        Commands::Out { at } => clock.clock_out(*at)?,
+        Commands::In { at, .. } => {
+            clock.clock_in(*at)?;
+        }
+        Commands::Out { at, .. } => {
+            clock.clock_out(*at)?;
+        }
         Commands::TimeClocked { granularity } => clock.time_clocked(&granularity.into())?,
+        Commands::Average { granularity } => clock.average(&granularity.into())?,
+        Commands::Pay { granularity, rate } => {
+            clock.pay(&granularity.into(), *rate)?;
+        }
+        Commands::Report { month } => clock.report(*month)?,
+        Commands::Streak => clock.streak()?,
+        Commands::Gaps => clock.gaps()?,
+        Commands::Import { file, force } => clock.import_csv(file, *force)?,
         Commands::Raw => clock.raw_timesheet()?,
         Commands::RunningTime => clock.running_time()?,
-        Commands::Timesheet { on } => clock.timesheet(*on)?,
-        Commands::Watch { hours } => clock.watch(hours),
+        Commands::Timesheet { on, detailed } => clock.timesheet(*on, *detailed)?,
+        Commands::Watch { hours } => clock.watch(hours)?,
         Commands::File => clock.print_file(),
+        Commands::Profiles => clock.profiles()?,
     }
 
     Ok(())
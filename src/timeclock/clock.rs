@@ -1,11 +1,13 @@
 use core::time;
 
 use anyhow::Result;
-use chrono::{Local, TimeDelta};
+use chrono::{Datelike, Local, TimeDelta, Utc};
 use cli_table::{format::Justify, print_stdout, Cell, Color, Style, Table};
+use serde::Serialize;
 
 use crate::Granularity;
 
+use super::notify::*;
 use super::timesheet::*;
 /// Debug mode.
 pub enum Debug {
@@ -22,18 +24,274 @@ impl Debug {
     }
 }
 
-fn print_hms(time: TimeDelta) {
+/// Profile used when none is given.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Directory within the data directory that holds a timesheet per profile.
+const PROFILES_DIR: &str = "clocker";
+
+/// Resolves the timesheet for a profile, `<data_dir>/clocker/<profile>.json`.
+pub fn profile_path(data_dir: &std::path::Path, profile: &str) -> Result<std::path::PathBuf> {
+    if profile.is_empty() || profile.starts_with('.') || profile.contains(['/', '\\']) {
+        anyhow::bail!("Invalid profile name: {:?}", profile);
+    }
+
+    Ok(data_dir
+        .join(PROFILES_DIR)
+        .join(format!("{}.json", profile)))
+}
+
+/// How durations are printed.
+#[derive(Clone, Copy, Default)]
+pub enum Format {
+    /// `HH:MM:SS`
+    #[default]
+    Hms,
+    /// ISO 8601 durations, such as `PT8H30M`.
+    Iso8601,
+}
+
+/// How results are reported.
+#[derive(Clone, Copy, Default)]
+pub enum Output {
+    /// Messages meant for people.
+    #[default]
+    Text,
+    /// JSON meant for other tools.
+    Json,
+}
+
+/// Structured confirmation of a clock in or out.
+#[derive(Serialize)]
+struct Clocked {
+    action: &'static str,
+    time: DateTime,
+    /// Length of the session closed by a clock out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seconds: Option<i64>,
+}
+
+fn format_hms(time: TimeDelta) -> String {
     let hh = time.num_hours();
     let mm = time.num_minutes() % 60;
     let ss = time.num_seconds() % 60;
 
-    println!("{:02}:{:02}:{:02}", hh, mm, ss);
+    format!("{:02}:{:02}:{:02}", hh, mm, ss)
+}
+
+/// Formats a duration as an ISO 8601 duration, such as `PT8H30M`, to the nearest
+/// second below.
+fn format_iso8601(time: TimeDelta) -> String {
+    let sign = if time < TimeDelta::zero() { "-" } else { "" };
+    let time = time.abs();
+    let hh = time.num_hours();
+    let mm = time.num_minutes() % 60;
+    let ss = time.num_seconds() % 60;
+
+    let mut duration = format!("{}PT", sign);
+    if hh > 0 {
+        duration += &format!("{}H", hh);
+    }
+    if mm > 0 {
+        duration += &format!("{}M", mm);
+    }
+    if ss > 0 || (hh == 0 && mm == 0) {
+        duration += &format!("{}S", ss);
+    }
+
+    duration
+}
+
+fn format_local_time(time: &DateTime) -> String {
+    time.with_timezone(&Local).format("%H:%M:%S").to_string()
+}
+
+/// Builds the rows of the detailed timesheet: one row per session, followed by
+/// a subtotal row for each day that has any sessions. Durations are rendered with
+/// `format_duration`.
+fn detailed_rows(
+    timesheet: &Timesheet,
+    on: Option<chrono::NaiveDate>,
+    now: DateTime,
+    max_session: Option<TimeDelta>,
+    format_duration: impl Fn(TimeDelta) -> String,
+) -> Vec<[String; 5]> {
+    let mut rows = Vec::new();
+
+    for (day, sessions) in timesheet.weekly_sessions(on) {
+        if sessions.is_empty() {
+            continue;
+        }
+
+        let date = day.format("%a %Y-%m-%d").to_string();
+        let mut subtotal = TimeDelta::zero();
+
+        for session in sessions {
+            // A running session stays on the day it started, even overnight, so
+            // its clock in and running length show together.
+            let duration = session.duration(now, max_session);
+            let (end, duration) = match (session.end, duration) {
+                (End::Out(end), Some(duration)) => {
+                    subtotal += duration;
+                    (format_local_time(&end), format_duration(duration))
+                }
+                (End::Running, Some(duration)) => {
+                    subtotal += duration;
+                    (
+                        String::new(),
+                        format!("{} (running)", format_duration(duration)),
+                    )
+                }
+                _ => (String::new(), String::new()),
+            };
+
+            rows.push([
+                date.clone(),
+                format_local_time(&session.start),
+                end,
+                duration,
+                String::new(),
+            ]);
+        }
+
+        rows.push([
+            date,
+            String::new(),
+            String::new(),
+            String::new(),
+            format_duration(subtotal),
+        ]);
+    }
+
+    rows
+}
+
+fn print_currency(amount: f64) {
+    println!("${:.2}", amount);
+}
+
+/// Converts a duration to fractional hours, keeping the sub-hour remainder.
+fn hours(time: TimeDelta) -> f64 {
+    time.num_seconds() as f64 / 3600.0
+}
+
+/// Converts a local time to UTC. Times skipped by a DST change don't exist, and
+/// repeated times are taken as the first occurrence.
+fn local_to_utc(at: chrono::NaiveDateTime) -> Result<DateTime> {
+    match at.and_local_timezone(Local).earliest() {
+        Some(at) => Ok(at.with_timezone(&Utc)),
+        None => anyhow::bail!("{} doesn't exist in the local timezone", at),
+    }
+}
+
+/// Ensures a clock doesn't come before the last action on the timesheet, which
+/// would put it out of order once saved.
+fn check_after_last_action(timesheet: &Timesheet, at: DateTime) -> Result<()> {
+    if let Some(last_action) = timesheet.last_action() {
+        if at < *last_action.time() {
+            anyhow::bail!(
+                "Can't clock at {}, before the last clock at {}",
+                at.with_timezone(&Local),
+                last_action.time().with_timezone(&Local)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensures a requested period is well-formed.
+fn check_period(worked: &This) -> Result<()> {
+    if let This::Range(from, to) = worked {
+        if from > to {
+            anyhow::bail!("Start of range ({}) is after its end ({})", from, to);
+        }
+    }
+
+    Ok(())
+}
+
+/// Upgrades a serialized timesheet to the current version, one version at a time.
+fn migrate(mut timesheet: serde_json::Value) -> Result<Timesheet> {
+    let mut version = match timesheet.get("version") {
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Invalid timesheet version: {}", version))?,
+        None => 0,
+    };
+
+    if version > TIMESHEET_VERSION {
+        anyhow::bail!(
+            "Timesheet version {} is newer than this version of clocker supports ({})",
+            version,
+            TIMESHEET_VERSION
+        );
+    }
+
+    while version < TIMESHEET_VERSION {
+        timesheet = match version {
+            // Local offsets are normalized to UTC when the actions are read, so
+            // only the version field is new.
+            0 => timesheet,
+            _ => unreachable!("No migration from timesheet version {}", version),
+        };
+        version += 1;
+    }
+
+    Ok(serde_json::from_value(timesheet)?)
+}
+
+/// Parses `action,timestamp` rows, such as `In,2024-03-11T09:00:00Z`, into a
+/// timesheet. An optional header row and blank lines are skipped.
+fn parse_csv(csv: &str) -> Result<Timesheet> {
+    let mut timesheet = Timesheet::default();
+
+    for (index, row) in csv.lines().enumerate() {
+        let line = index + 1;
+        let row = row.trim();
+        if row.is_empty() || (line == 1 && row.eq_ignore_ascii_case("action,timestamp")) {
+            continue;
+        }
+
+        let Some((action, time)) = row.split_once(',') else {
+            anyhow::bail!("Line {}: expected `action,timestamp`, got `{}`", line, row);
+        };
+        let time = chrono::DateTime::parse_from_rfc3339(time.trim())
+            .map_err(|err| anyhow::anyhow!("Line {}: invalid timestamp: {}", line, err))?
+            .with_timezone(&Utc);
+
+        if let Some(last_action) = timesheet.last_action() {
+            if time < *last_action.time() {
+                anyhow::bail!("Line {}: clocks must be in order", line);
+            }
+        }
+
+        match (
+            action.trim().to_ascii_lowercase().as_str(),
+            timesheet.last_action(),
+        ) {
+            ("in", Some(Action::In(_))) => {
+                anyhow::bail!("Line {}: clocked in while already clocked in", line)
+            }
+            ("out", Some(Action::Out(_))) => {
+                anyhow::bail!("Line {}: clocked out while already clocked out", line)
+            }
+            ("in", _) => timesheet.clock_in(time),
+            ("out", _) => timesheet.clock_out(time),
+            (action, _) => anyhow::bail!("Line {}: unknown action `{}`", line, action),
+        }
+    }
+
+    Ok(timesheet)
 }
 
 /// Timeclock service
 pub struct Timeclock<'a> {
     timesheet_path: &'a std::path::Path,
     debug: Debug,
+    format: Format,
+    output: Output,
+    max_session: Option<TimeDelta>,
 }
 
 impl<'a> Timeclock<'a> {
@@ -42,78 +300,272 @@ impl<'a> Timeclock<'a> {
         Self {
             timesheet_path,
             debug,
+            format: Format::default(),
+            output: Output::default(),
+            max_session: None,
         }
     }
 
-    /// Clocks in the user.
-    pub fn clock_in(&self, at: Option<chrono::NaiveDateTime>) -> Result<()> {
-        let mut timesheet = self.get_timesheet()?;
+    /// Sets how durations are printed.
+    pub fn with_format(self, format: Format) -> Self {
+        Self { format, ..self }
+    }
+
+    /// Sets how results are reported.
+    pub fn with_output(self, output: Output) -> Self {
+        Self { output, ..self }
+    }
 
-        if let Some(Action::In(_)) = timesheet.last_action() {
-            anyhow::bail!("You are already clocked in");
+    /// Caps how much time a single session counts for.
+    pub fn with_max_session(self, max_session: Option<TimeDelta>) -> Self {
+        Self {
+            max_session,
+            ..self
         }
+    }
+
+    /// Clocks in the user, returning the confirmation shown to them.
+    pub fn clock_in(&self, at: Option<chrono::NaiveDateTime>) -> Result<String> {
+        let at = self.update_timesheet(|timesheet| {
+            if let Some(Action::In(_)) = timesheet.last_action() {
+                anyhow::bail!("You are already clocked in");
+            }
 
-        let at = at
-            .map(|at| at.and_local_timezone(Local).unwrap())
-            .unwrap_or(Local::now());
-        timesheet.clock_in(at);
-        self.save_timesheet(&timesheet)?;
+            let at = at.map(local_to_utc).transpose()?.unwrap_or(Utc::now());
+            check_after_last_action(timesheet, at)?;
+            timesheet.clock_in(at);
 
-        Ok(())
+            Ok(at)
+        })?;
+
+        self.confirm(Clocked {
+            action: "in",
+            time: at,
+            seconds: None,
+        })
     }
 
-    /// Clocks out the user.
-    pub fn clock_out(&self, at: Option<chrono::NaiveDateTime>) -> Result<()> {
-        let mut timesheet = self.get_timesheet()?;
+    /// Clocks out the user, returning the confirmation shown to them, which
+    /// includes the length of the session it closed.
+    pub fn clock_out(&self, at: Option<chrono::NaiveDateTime>) -> Result<String> {
+        let (at, elapsed) = self.update_timesheet(|timesheet| {
+            let clock_in = match timesheet.last_action() {
+                Some(Action::Out(_)) => anyhow::bail!("You are already clocked out"),
+                Some(Action::In(clock_in)) => Some(*clock_in),
+                None => None,
+            };
 
-        if let Some(Action::Out(_)) = timesheet.last_action() {
-            anyhow::bail!("You are already clocked out");
-        }
+            let at = at.map(local_to_utc).transpose()?.unwrap_or(Utc::now());
+            check_after_last_action(timesheet, at)?;
+            timesheet.clock_out(at);
 
-        let at = at
-            .map(|at| at.and_local_timezone(Local).unwrap())
-            .unwrap_or(Local::now());
-        timesheet.clock_out(at);
-        self.save_timesheet(&timesheet)?;
+            Ok((
+                at,
+                clock_in.map(|clock_in| at.signed_duration_since(clock_in)),
+            ))
+        })?;
 
-        Ok(())
+        self.confirm(Clocked {
+            action: "out",
+            time: at,
+            seconds: elapsed.map(|elapsed| elapsed.num_seconds()),
+        })
+    }
+
+    /// Prints the confirmation of a clock in or out, and returns it.
+    fn confirm(&self, clocked: Clocked) -> Result<String> {
+        let confirmation = self.confirmation(&clocked)?;
+        println!("{}", confirmation);
+
+        Ok(confirmation)
+    }
+
+    /// Formats the confirmation of a clock in or out for the output mode.
+    fn confirmation(&self, clocked: &Clocked) -> Result<String> {
+        let confirmation = match self.output {
+            Output::Json => serde_json::to_string(clocked)?,
+            Output::Text => {
+                let time = format_local_time(&clocked.time);
+                match clocked.seconds.and_then(TimeDelta::try_seconds) {
+                    Some(elapsed) => format!(
+                        "Clocked {} at {} after {}",
+                        clocked.action,
+                        time,
+                        self.format_duration(elapsed)
+                    ),
+                    None => format!("Clocked {} at {}", clocked.action, time),
+                }
+            }
+        };
+
+        Ok(confirmation)
     }
 
     /// Prints the total time worked.
     pub fn time_clocked(&self, worked: &This) -> Result<()> {
+        check_period(worked)?;
+
         let timesheet = self.get_timesheet()?;
-        let total_time = timesheet.total_time(worked);
+        let total_time = timesheet.total_time(worked, self.max_session);
 
-        print_hms(total_time);
+        self.print_duration(total_time);
 
         Ok(())
     }
 
+    /// Prints the average time worked per day worked.
+    pub fn average(&self, worked: &This) -> Result<()> {
+        check_period(worked)?;
+
+        let timesheet = self.get_timesheet()?;
+        let average = timesheet.average_per_day(worked, self.max_session);
+
+        self.print_duration(average);
+
+        Ok(())
+    }
+
+    /// Prints and returns the pay earned for the time worked at an hourly rate.
+    pub fn pay(&self, worked: &This, rate: f64) -> Result<f64> {
+        check_period(worked)?;
+
+        let timesheet = self.get_timesheet()?;
+        let pay = hours(timesheet.total_time(worked, self.max_session)) * rate;
+
+        print_currency(pay);
+
+        Ok(pay)
+    }
+
     /// Returns the total time worked today.
     pub fn running_time(&self) -> Result<()> {
         let timesheet = self.get_timesheet()?;
-        let running_time = timesheet.running_time().unwrap_or(TimeDelta::zero());
+        let running_time = timesheet
+            .running_time(self.max_session)
+            .unwrap_or(TimeDelta::zero());
 
-        print_hms(running_time);
+        self.print_duration(running_time);
 
         Ok(())
     }
 
+    /// Prints the time worked in each week of a month, and in the whole month.
+    pub fn report(&self, month: Option<chrono::NaiveDate>) -> Result<()> {
+        let month = month.unwrap_or(Local::now().date_naive());
+        let timesheet = self.get_timesheet()?;
+        let report = timesheet.monthly_report(month.year(), month.month(), self.max_session);
+        let total_time: TimeDelta = report.iter().map(|(_, time_worked)| *time_worked).sum();
+
+        let mut rows = report
+            .iter()
+            .map(|(week, time_worked)| {
+                vec![
+                    format!("{}-W{:02}", week.year(), week.week()).cell(),
+                    self.format_duration(*time_worked)
+                        .cell()
+                        .justify(Justify::Right),
+                ]
+            })
+            .collect::<Vec<_>>();
+        rows.push(vec![
+            "Total".cell().bold(true),
+            self.format_duration(total_time)
+                .cell()
+                .justify(Justify::Right)
+                .bold(true),
+        ]);
+
+        let chart = rows
+            .table()
+            .title(vec![
+                month
+                    .format("%B %Y")
+                    .cell()
+                    .bold(true)
+                    .foreground_color(Some(Color::Green)),
+                "Hours"
+                    .cell()
+                    .bold(true)
+                    .foreground_color(Some(Color::Green)),
+            ])
+            .bold(true);
+
+        print_stdout(chart)?;
+        Ok(())
+    }
+
+    /// Prints the current and longest streaks of consecutive days worked.
+    pub fn streak(&self) -> Result<()> {
+        let timesheet = self.get_timesheet()?;
+        let (current, longest) = timesheet.streaks();
+
+        println!("Current streak: {} day(s)", current);
+        println!("Longest streak: {} day(s)", longest);
+
+        Ok(())
+    }
+
+    /// Prints any clock ins that are missing a clock out, failing if there are any.
+    pub fn gaps(&self) -> Result<()> {
+        let timesheet = self.get_timesheet()?;
+        let gaps = timesheet.gaps();
+
+        for gap in &gaps {
+            println!(
+                "{}: clocked in at {} without clocking out",
+                gap.index,
+                gap.clock_in
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+            );
+        }
+
+        if !gaps.is_empty() {
+            anyhow::bail!("Found {} clock in(s) missing a clock out", gaps.len());
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the timesheet with one imported from a CSV file. Refuses to
+    /// replace a timesheet that already has clocks unless forced.
+    pub fn import_csv(&self, path: &std::path::Path, force: bool) -> Result<()> {
+        let imported = parse_csv(&std::fs::read_to_string(path)?)?;
+
+        self.update_timesheet(|timesheet| {
+            if !timesheet.clocks.is_empty() && !force {
+                anyhow::bail!(
+                    "The timesheet already has {} clocks, use --force to replace them",
+                    timesheet.clocks.len()
+                );
+            }
+
+            *timesheet = imported;
+
+            Ok(())
+        })
+    }
+
     /// Prints the raw timesheet.
     pub fn raw_timesheet(&self) -> Result<()> {
         let timesheet = &self.get_timesheet()?;
-        let timesheet = serde_json::to_string(&timesheet)?;
+        let timesheet = serde_json::to_string(&timesheet.versioned())?;
 
         println!("{}", timesheet);
         Ok(())
     }
 
-    pub fn timesheet(&self, on: Option<chrono::NaiveDate>) -> Result<()> {
+    pub fn timesheet(&self, on: Option<chrono::NaiveDate>, detailed: bool) -> Result<()> {
         let timesheet = &self.get_timesheet()?;
+
+        if detailed {
+            return self.detailed_timesheet(timesheet, on);
+        }
+
         let weekly_hours = timesheet
-            .weekly_hours(on)
+            .weekly_hours(on, self.max_session)
             .iter()
-            .map(|hours| hours.cell())
+            .map(|hours| self.format_duration(*hours).cell())
             .collect::<Vec<_>>();
         let chart = vec![weekly_hours]
             .table()
@@ -153,12 +605,179 @@ impl<'a> Timeclock<'a> {
         Ok(())
     }
 
-    pub fn watch(&self, _hours: &usize) {}
+    fn detailed_timesheet(
+        &self,
+        timesheet: &Timesheet,
+        on: Option<chrono::NaiveDate>,
+    ) -> Result<()> {
+        let rows = detailed_rows(timesheet, on, Utc::now(), self.max_session, |time| {
+            self.format_duration(time)
+        })
+        .into_iter()
+        .map(|[date, clock_in, clock_out, duration, total]| {
+            vec![
+                date.cell(),
+                clock_in.cell(),
+                clock_out.cell(),
+                duration.cell().justify(Justify::Right),
+                total.cell().justify(Justify::Right).bold(true),
+            ]
+        })
+        .collect::<Vec<_>>();
+        let chart = rows
+            .table()
+            .title(vec![
+                "Day".cell().bold(true).foreground_color(Some(Color::Green)),
+                "In".cell().bold(true).foreground_color(Some(Color::Green)),
+                "Out".cell().bold(true).foreground_color(Some(Color::Green)),
+                "Duration"
+                    .cell()
+                    .bold(true)
+                    .foreground_color(Some(Color::Green)),
+                "Total"
+                    .cell()
+                    .bold(true)
+                    .foreground_color(Some(Color::Yellow)),
+            ])
+            .bold(true);
+
+        print_stdout(chart)?;
+        Ok(())
+    }
+
+    /// Waits until the specified number of hours have been worked this week,
+    /// then notifies the user.
+    pub fn watch(&self, hours: &usize) -> Result<()> {
+        self.watch_with(hours, &Desktop, time::Duration::from_secs(60), usize::MAX)
+    }
+
+    /// Checks the time worked up to `checks` times, `interval` apart, and fails
+    /// if the goal still hasn't been reached by the last check.
+    fn watch_with(
+        &self,
+        hours: &usize,
+        notifier: &impl Notifier,
+        interval: time::Duration,
+        checks: usize,
+    ) -> Result<()> {
+        let goal = TimeDelta::try_hours(*hours as i64)
+            .ok_or_else(|| anyhow::anyhow!("{} hours is too long to watch for", hours))?;
+
+        for check in 0..checks {
+            if check > 0 {
+                std::thread::sleep(interval);
+            }
+
+            let total_time = self
+                .get_timesheet()?
+                .total_time(&This::Week, self.max_session);
+
+            if self.debug.is_on() {
+                eprintln!(
+                    "Worked {} of {} this week",
+                    self.format_duration(total_time),
+                    self.format_duration(goal)
+                );
+            }
+
+            if total_time >= goal {
+                let title = format!("{} hour goal reached", hours);
+                let body = format!("Clocked {} this week.", self.format_duration(total_time));
+                println!("{} {}", title, body);
+
+                // Notifying is best-effort; the goal was still reached.
+                if let Err(err) = notifier.notify(&title, &body) {
+                    eprintln!("Unable to send notification: {}", err);
+                }
+
+                return Ok(());
+            }
+        }
+
+        anyhow::bail!("Stopped watching before reaching {} hours", hours)
+    }
 
     pub fn print_file(&self) {
         println!("{}", self.timesheet_path.display());
     }
 
+    /// Prints the profiles that have a timesheet, marking the current one.
+    pub fn profiles(&self) -> Result<()> {
+        let Some(profiles_dir) = self.timesheet_path.parent() else {
+            return Ok(());
+        };
+        if !profiles_dir.exists() {
+            return Ok(());
+        }
+
+        let mut profiles = std::fs::read_dir(profiles_dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        profiles.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+        profiles.sort();
+
+        for path in profiles {
+            let Some(profile) = path.file_stem() else {
+                continue;
+            };
+            let marker = if path == self.timesheet_path {
+                "*"
+            } else {
+                " "
+            };
+            println!("{} {}", marker, profile.to_string_lossy());
+        }
+
+        Ok(())
+    }
+
+    fn format_duration(&self, time: TimeDelta) -> String {
+        match self.format {
+            Format::Hms => format_hms(time),
+            Format::Iso8601 => format_iso8601(time),
+        }
+    }
+
+    fn print_duration(&self, time: TimeDelta) {
+        println!("{}", self.format_duration(time));
+    }
+
+    /// Loads, modifies and saves the timesheet while holding the lock, so that
+    /// concurrent invocations wait for each other instead of dropping actions.
+    fn update_timesheet<T>(&self, f: impl FnOnce(&mut Timesheet) -> Result<T>) -> Result<T> {
+        let _lock = self.lock_timesheet()?;
+
+        let mut timesheet = self.get_timesheet()?;
+        let ret = f(&mut timesheet)?;
+        self.save_timesheet(&mut timesheet)?;
+
+        Ok(ret)
+    }
+
+    /// Blocks until an exclusive advisory lock on the timesheet is acquired. The
+    /// lock is released when the returned file is dropped.
+    fn lock_timesheet(&self) -> Result<std::fs::File> {
+        let lock_path = self.sibling_path(".lock");
+
+        if self.debug.is_on() {
+            eprintln!("Locking timesheet with: {:?}", lock_path);
+        }
+
+        // The first save for a profile may need to create its directory.
+        if let Some(profiles_dir) = self.timesheet_path.parent() {
+            std::fs::create_dir_all(profiles_dir)?;
+        }
+
+        let lock = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lock_path)?;
+        lock.lock()?;
+
+        Ok(lock)
+    }
+
     fn get_timesheet(&self) -> Result<Timesheet> {
         if self.debug.is_on() {
             eprintln!("Loading timesheet from: {:?}", self.timesheet_path);
@@ -167,23 +786,58 @@ impl<'a> Timeclock<'a> {
         if self.timesheet_path.exists() {
             let timesheet = std::fs::read_to_string(self.timesheet_path)?;
             let timesheet = serde_json::from_str(&timesheet)?;
-            Ok(timesheet)
+            migrate(timesheet)
         } else {
             eprintln!("No timesheet found, creating a new one.");
             Ok(Timesheet::default())
         }
     }
 
-    fn save_timesheet(&self, timesheet: &Timesheet) -> Result<()> {
+    fn save_timesheet(&self, timesheet: &mut Timesheet) -> Result<()> {
         if self.debug.is_on() {
             eprintln!("Saving timesheet to: {:?}", self.timesheet_path);
         }
 
-        let timesheet = serde_json::to_string_pretty(timesheet)?;
-        std::fs::write(self.timesheet_path, timesheet)?;
+        timesheet.canonicalize();
+        self.backup_timesheet();
+
+        // Write to a temporary file and move it into place, so that reads, which
+        // don't take the lock, never see a partially written timesheet.
+        let temp_path = self.sibling_path(".tmp");
+        let timesheet = serde_json::to_string_pretty(&timesheet.versioned())?;
+        std::fs::write(&temp_path, timesheet)?;
+        std::fs::rename(&temp_path, self.timesheet_path)?;
 
         Ok(())
     }
+
+    /// Copies the current timesheet, if any, to a single rolling backup. Failing
+    /// to back up only warns, so it never stops a save.
+    fn backup_timesheet(&self) {
+        if !self.timesheet_path.exists() {
+            return;
+        }
+
+        let backup_path = self.sibling_path(".bak");
+        if self.debug.is_on() {
+            eprintln!("Backing up timesheet to: {:?}", backup_path);
+        }
+
+        if let Err(err) = std::fs::copy(self.timesheet_path, &backup_path) {
+            eprintln!(
+                "Unable to back up timesheet to {}: {}",
+                backup_path.display(),
+                err
+            );
+        }
+    }
+
+    /// Returns the path of a file kept next to the timesheet, named after it.
+    fn sibling_path(&self, suffix: &str) -> std::path::PathBuf {
+        let mut path = self.timesheet_path.as_os_str().to_owned();
+        path.push(suffix);
+        path.into()
+    }
 }
 
 #[cfg(test)]
@@ -192,6 +846,17 @@ mod timeclock_tests {
     use chrono::{DurationRound, Timelike};
     use tempfile::tempdir;
 
+    /// Returns the given hour of a day in the local timezone.
+    fn local(year: i32, month: u32, day: u32, hour: u32) -> DateTime {
+        chrono::NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
     fn round(time: DateTime) -> DateTime {
         time.duration_round(TimeDelta::try_seconds(1).unwrap())
             .unwrap()
@@ -207,6 +872,55 @@ mod timeclock_tests {
         ret
     }
 
+    #[test]
+    fn iso8601_hours_and_minutes() {
+        let time = TimeDelta::try_minutes(8 * 60 + 30).unwrap();
+        assert_eq!(format_iso8601(time), "PT8H30M");
+    }
+
+    #[test]
+    fn iso8601_whole_hour() {
+        let time = TimeDelta::try_hours(1).unwrap();
+        assert_eq!(format_iso8601(time), "PT1H");
+    }
+
+    #[test]
+    fn iso8601_under_a_minute() {
+        assert_eq!(format_iso8601(TimeDelta::try_seconds(42).unwrap()), "PT42S");
+        assert_eq!(format_iso8601(TimeDelta::zero()), "PT0S");
+    }
+
+    #[test]
+    fn profile_paths() -> Result<()> {
+        let data_dir = std::path::Path::new("data");
+
+        assert_eq!(
+            profile_path(data_dir, DEFAULT_PROFILE)?,
+            data_dir.join("clocker").join("default.json")
+        );
+        assert_eq!(
+            profile_path(data_dir, "side-project")?,
+            data_dir.join("clocker").join("side-project.json")
+        );
+        assert!(profile_path(data_dir, "").is_err());
+        assert!(profile_path(data_dir, "../work").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn clock_in_creates_profile_dir() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let data_dir = timesheet_path.parent().unwrap();
+            let work = profile_path(data_dir, "work")?;
+            Timeclock::new(&work, Debug::Off).clock_in(None)?;
+
+            assert!(work.exists());
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn clock_in() -> Result<()> {
         with_temp(|timesheet_path| {
@@ -215,7 +929,7 @@ mod timeclock_tests {
             let timesheet = timeclock.get_timesheet()?;
 
             match timesheet.last_action().unwrap() {
-                Action::In(time) => assert_eq!(round(*time), round(Local::now())),
+                Action::In(time) => assert_eq!(round(*time), round(Utc::now())),
                 _ => panic!("Expected last action to be a clock in"),
             }
 
@@ -223,6 +937,27 @@ mod timeclock_tests {
         })
     }
 
+    #[test]
+    fn clock_in_at_time_skipped_by_dst_fails() -> Result<()> {
+        // Only finds a skipped time when run in a timezone with DST.
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 30, 0)
+            .unwrap();
+        let skipped = (0..366 * 24)
+            .map(|hours| start + TimeDelta::try_hours(hours).unwrap())
+            .find(|at| at.and_local_timezone(Local).earliest().is_none());
+
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            if let Some(at) = skipped {
+                assert!(timeclock.clock_in(Some(at)).is_err());
+            }
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn clock_in_twice_fails() -> Result<()> {
         with_temp(|timesheet_path| {
@@ -245,7 +980,7 @@ mod timeclock_tests {
             let timesheet = timeclock.get_timesheet()?;
 
             match timesheet.last_action().unwrap() {
-                Action::Out(time) => assert_eq!(round(*time), round(Local::now())),
+                Action::Out(time) => assert_eq!(round(*time), round(Utc::now())),
                 _ => panic!("Expected last action to be a clock out"),
             }
 
@@ -253,6 +988,103 @@ mod timeclock_tests {
         })
     }
 
+    #[test]
+    fn concurrent_clocks_are_not_lost() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let barrier = std::sync::Barrier::new(2);
+
+            std::thread::scope(|scope| {
+                let clock_in = scope.spawn(|| {
+                    let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+                    barrier.wait();
+                    timeclock.clock_in(None)
+                });
+                let clock_out = scope.spawn(|| {
+                    let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+                    barrier.wait();
+                    timeclock.clock_out(None)
+                });
+
+                clock_in.join().unwrap()?;
+                clock_out.join().unwrap()
+            })?;
+
+            let timesheet = Timeclock::new(timesheet_path, Debug::Off).get_timesheet()?;
+            assert_eq!(timesheet.clocks.len(), 2);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn clock_out_reports_session_length() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            let clock_in = Local::now()
+                .checked_sub_signed(TimeDelta::try_minutes(8 * 60 + 30).unwrap())
+                .unwrap()
+                .naive_local();
+            let clock_out = clock_in + TimeDelta::try_hours(8).unwrap();
+
+            timeclock.clock_in(Some(clock_in))?;
+            let confirmation = timeclock.clock_out(Some(clock_out))?;
+
+            assert_eq!(
+                confirmation,
+                format!(
+                    "Clocked out at {} after 08:00:00",
+                    format_local_time(&local_to_utc(clock_out)?)
+                )
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn clock_out_without_clock_in() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off).with_output(Output::Json);
+
+            let confirmation = timeclock.clock_out(None)?;
+
+            assert!(!confirmation.contains("seconds"));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn clock_out_before_clock_in_fails() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            let day = chrono::NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+
+            timeclock.clock_in(day.and_hms_opt(9, 0, 0))?;
+
+            assert!(timeclock.clock_out(day.and_hms_opt(7, 0, 0)).is_err());
+            assert_eq!(timeclock.get_timesheet()?.clocks.len(), 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn clock_in_before_last_clock_out_fails() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            let day = chrono::NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+
+            timeclock.clock_in(day.and_hms_opt(9, 0, 0))?;
+            timeclock.clock_out(day.and_hms_opt(17, 0, 0))?;
+
+            assert!(timeclock.clock_in(day.and_hms_opt(12, 0, 0)).is_err());
+            assert_eq!(timeclock.get_timesheet()?.clocks.len(), 2);
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn clock_out_twice_fails() -> Result<()> {
         with_temp(|timesheet_path| {
@@ -266,6 +1098,168 @@ mod timeclock_tests {
         })
     }
 
+    #[test]
+    fn versionless_timesheet_is_migrated() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            std::fs::write(
+                timesheet_path,
+                r#"{"clocks":[{"In":"2024-03-10T09:00:00+09:00"},{"Out":"2024-03-10T17:00:00+09:00"}]}"#,
+            )?;
+
+            let mut timesheet = timeclock.get_timesheet()?;
+            assert_eq!(timesheet.clocks.len(), 2);
+
+            timeclock.save_timesheet(&mut timesheet)?;
+            let saved: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(timesheet_path)?)?;
+            assert_eq!(
+                saved.get("version").and_then(|version| version.as_u64()),
+                Some(TIMESHEET_VERSION)
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn newer_timesheet_version_fails() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            std::fs::write(
+                timesheet_path,
+                format!(r#"{{"version":{},"clocks":[]}}"#, TIMESHEET_VERSION + 1),
+            )?;
+
+            assert!(timeclock.get_timesheet().is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn gaps_fail() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            timeclock.gaps()?;
+
+            let mut timesheet = timeclock.get_timesheet()?;
+            let now = Utc::now();
+            timesheet.clock_in(now);
+            timesheet.clock_in(now);
+            timeclock.save_timesheet(&mut timesheet)?;
+
+            assert!(timeclock.gaps().is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn saved_clocks_are_sorted() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            let now = Utc::now();
+            let hours_ago = |hours| {
+                now.checked_sub_signed(TimeDelta::try_hours(hours).unwrap())
+                    .unwrap()
+            };
+
+            let mut timesheet = timeclock.get_timesheet()?;
+            timesheet.clock_in(hours_ago(8));
+            timesheet.clock_out(hours_ago(1));
+            // Clocked out for lunch after the fact.
+            timesheet.clock_out(hours_ago(4));
+            timeclock.save_timesheet(&mut timesheet)?;
+
+            let saved: Timesheet = serde_json::from_str(&std::fs::read_to_string(timesheet_path)?)?;
+            assert_eq!(
+                saved.clocks,
+                [
+                    Action::In(hours_ago(8)),
+                    Action::Out(hours_ago(4)),
+                    Action::Out(hours_ago(1)),
+                ]
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn import_csv() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            let csv_path = timesheet_path.with_extension("csv");
+            std::fs::write(
+                &csv_path,
+                "action,timestamp\n\
+                 In,2024-03-11T09:00:00Z\n\
+                 Out,2024-03-11T17:00:00+00:00\n\
+                 in,2024-03-12T18:00:00+09:00\n",
+            )?;
+
+            timeclock.import_csv(&csv_path, false)?;
+            let timesheet = timeclock.get_timesheet()?;
+
+            assert_eq!(timesheet.clocks.len(), 3);
+            assert_eq!(
+                timesheet.last_action(),
+                Some(&Action::In(
+                    "2024-03-12T09:00:00Z".parse::<DateTime>().unwrap()
+                ))
+            );
+
+            // Importing again would throw away what's there.
+            assert!(timeclock.import_csv(&csv_path, false).is_err());
+            timeclock.import_csv(&csv_path, true)?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn import_csv_reports_bad_line() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            let csv_path = timesheet_path.with_extension("csv");
+            std::fs::write(
+                &csv_path,
+                "In,2024-03-11T09:00:00Z\n\
+                 Out,2024-03-11T17:00:00Z\n\
+                 Out,2024-03-11T18:00:00Z\n",
+            )?;
+
+            let err = timeclock.import_csv(&csv_path, false).unwrap_err();
+            assert!(err.to_string().starts_with("Line 3:"), "{}", err);
+            assert!(!timesheet_path.exists());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn backup_holds_previous_save() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            let backup_path = timesheet_path.with_extension("json.bak");
+
+            let mut timesheet = timeclock.get_timesheet()?;
+            timesheet.clock_in(Utc::now());
+            timeclock.save_timesheet(&mut timesheet)?;
+            assert!(!backup_path.exists());
+
+            let first_save = std::fs::read_to_string(timesheet_path)?;
+            timesheet.clock_out(Utc::now());
+            timeclock.save_timesheet(&mut timesheet)?;
+
+            assert_eq!(std::fs::read_to_string(&backup_path)?, first_save);
+            assert_ne!(std::fs::read_to_string(timesheet_path)?, first_save);
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn time_worked_today() -> Result<()> {
         with_temp(|timesheet_path| {
@@ -274,14 +1268,14 @@ mod timeclock_tests {
             // Setup
             {
                 let mut timesheet = timeclock.get_timesheet()?;
-                let now = Local::now().with_hour(12).unwrap();
+                let now = Local::now().with_hour(12).unwrap().with_timezone(&Utc);
                 let clock_in = now
                     .checked_sub_signed(TimeDelta::try_hours(8).unwrap())
                     .unwrap();
 
                 timesheet.clock_in(clock_in);
                 timesheet.clock_out(now);
-                timeclock.save_timesheet(&timesheet)?;
+                timeclock.save_timesheet(&mut timesheet)?;
             }
 
             // No assertions, just make sure it doesn't panic.
@@ -290,4 +1284,213 @@ mod timeclock_tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn time_clocked_rejects_inverted_range() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            let from = chrono::NaiveDate::from_ymd_opt(2024, 3, 20).unwrap();
+            let to = chrono::NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+
+            assert!(timeclock.time_clocked(&This::Range(from, to)).is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn detailed_rows_subtotal_per_day() {
+        let mut timesheet = Timesheet::default();
+        let tuesday = chrono::NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+
+        timesheet.clock_in(local(2024, 3, 12, 9));
+        timesheet.clock_out(local(2024, 3, 12, 12));
+        timesheet.clock_in(local(2024, 3, 12, 13));
+        timesheet.clock_out(local(2024, 3, 12, 15));
+
+        let rows = detailed_rows(&timesheet, Some(tuesday), Utc::now(), None, format_hms);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0][3], "03:00:00");
+        assert_eq!(rows[1][3], "02:00:00");
+        assert_eq!(rows[2][4], "05:00:00");
+
+        let rows = detailed_rows(&timesheet, Some(tuesday), Utc::now(), None, format_iso8601);
+
+        assert_eq!(rows[0][3], "PT3H");
+        assert_eq!(rows[2][4], "PT5H");
+    }
+
+    #[test]
+    fn detailed_rows_leave_out_missing_clock_outs() {
+        let mut timesheet = Timesheet::default();
+        let monday = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+
+        timesheet.clock_in(local(2024, 3, 11, 9));
+        timesheet.clock_in(local(2024, 3, 12, 9));
+        timesheet.clock_out(local(2024, 3, 12, 17));
+
+        let rows = detailed_rows(
+            &timesheet,
+            Some(monday),
+            local(2024, 3, 12, 18),
+            None,
+            format_hms,
+        );
+
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0][2], "");
+        assert_eq!(rows[0][3], "");
+        assert_eq!(rows[1][4], "00:00:00");
+        assert_eq!(rows[2][3], "08:00:00");
+        assert_eq!(rows[3][4], "08:00:00");
+    }
+
+    #[test]
+    fn detailed_rows_running_overnight() {
+        let mut timesheet = Timesheet::default();
+        let monday = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+
+        timesheet.clock_in(local(2024, 3, 11, 22));
+
+        let rows = detailed_rows(
+            &timesheet,
+            Some(monday),
+            local(2024, 3, 12, 3),
+            None,
+            format_hms,
+        );
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][1], format_local_time(&local(2024, 3, 11, 22)));
+        assert_eq!(rows[0][3], "05:00:00 (running)");
+        assert_eq!(rows[1][4], "05:00:00");
+    }
+
+    struct MockNotifier {
+        sent: std::cell::RefCell<Vec<(String, String)>>,
+        fail: bool,
+    }
+
+    impl MockNotifier {
+        fn new(fail: bool) -> Self {
+            Self {
+                sent: Default::default(),
+                fail,
+            }
+        }
+    }
+
+    impl Notifier for MockNotifier {
+        fn notify(&self, title: &str, body: &str) -> Result<()> {
+            self.sent
+                .borrow_mut()
+                .push((title.to_string(), body.to_string()));
+
+            if self.fail {
+                anyhow::bail!("No notification daemon");
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Works from 09:00 on Monday of the current week for the given hours.
+    fn worked_this_week(timeclock: &Timeclock, hours: u32) -> Result<()> {
+        let mut timesheet = timeclock.get_timesheet()?;
+        let monday = Local::now()
+            .date_naive()
+            .week(chrono::Weekday::Mon)
+            .first_day();
+        let at = |hour| local(monday.year(), monday.month(), monday.day(), hour);
+
+        timesheet.clock_in(at(9));
+        timesheet.clock_out(at(9 + hours));
+        timeclock.save_timesheet(&mut timesheet)
+    }
+
+    #[test]
+    fn watch_notifies_when_goal_reached() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            worked_this_week(&timeclock, 2)?;
+
+            let notifier = MockNotifier::new(false);
+            timeclock.watch_with(&1, &notifier, time::Duration::ZERO, 1)?;
+
+            let sent = notifier.sent.borrow();
+            assert_eq!(sent.len(), 1);
+            assert!(sent[0].0.contains("1 hour"));
+            assert!(sent[0].1.contains("02:00:00"));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn watch_survives_failed_notification() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            worked_this_week(&timeclock, 2)?;
+
+            let notifier = MockNotifier::new(true);
+            timeclock.watch_with(&1, &notifier, time::Duration::ZERO, 1)?;
+
+            assert_eq!(notifier.sent.borrow().len(), 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn watch_formats_durations() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off).with_format(Format::Iso8601);
+            worked_this_week(&timeclock, 2)?;
+
+            let notifier = MockNotifier::new(false);
+            timeclock.watch_with(&1, &notifier, time::Duration::ZERO, 1)?;
+
+            assert_eq!(notifier.sent.borrow()[0].1, "Clocked PT2H this week.");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn watch_stops_after_last_check() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            worked_this_week(&timeclock, 2)?;
+
+            let notifier = MockNotifier::new(false);
+            let watched = timeclock.watch_with(&3, &notifier, time::Duration::ZERO, 2);
+
+            assert!(watched.is_err());
+            assert!(notifier.sent.borrow().is_empty());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn pay_for_partial_hours() -> Result<()> {
+        with_temp(|timesheet_path| {
+            let timeclock = Timeclock::new(timesheet_path, Debug::Off);
+            let day = chrono::NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+
+            // Setup
+            {
+                let mut timesheet = timeclock.get_timesheet()?;
+                timesheet.clock_in(local(2024, 3, 12, 9));
+                timesheet.clock_out(local(2024, 3, 12, 17) + TimeDelta::try_minutes(30).unwrap());
+                timeclock.save_timesheet(&mut timesheet)?;
+            }
+
+            let pay = timeclock.pay(&This::Range(day, day), 20.0)?;
+            assert_eq!(format!("{:.2}", pay), "170.00");
+
+            Ok(())
+        })
+    }
 }
@@ -1,4 +1,5 @@
 mod clock;
+mod notify;
 mod timesheet;
 
 pub use clock::*;
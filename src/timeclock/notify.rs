@@ -0,0 +1,29 @@
+use anyhow::Result;
+
+/// Sends notifications to the user.
+pub trait Notifier {
+    fn notify(&self, title: &str, body: &str) -> Result<()>;
+}
+
+/// Shows notifications on the desktop.
+pub struct Desktop;
+
+#[cfg(feature = "notify")]
+impl Notifier for Desktop {
+    fn notify(&self, title: &str, body: &str) -> Result<()> {
+        notify_rust::Notification::new()
+            .summary(title)
+            .body(body)
+            .show()?;
+
+        Ok(())
+    }
+}
+
+/// Built without desktop notification support, so there's nothing to show.
+#[cfg(not(feature = "notify"))]
+impl Notifier for Desktop {
+    fn notify(&self, _title: &str, _body: &str) -> Result<()> {
+        Ok(())
+    }
+}
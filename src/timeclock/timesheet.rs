@@ -1,16 +1,27 @@
 use core::time;
-use std::collections::VecDeque;
+use std::collections::{BTreeSet, HashSet, VecDeque};
 
-use chrono::{Datelike, Days, Local, NaiveDate, TimeDelta};
-use serde::{Deserialize, Serialize};
+use chrono::{Datelike, Days, FixedOffset, IsoWeek, Local, NaiveDate, NaiveTime, TimeDelta, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
 
-pub type DateTime = chrono::DateTime<Local>;
+/// Timestamps are stored in UTC, and only converted to local time for display
+/// and for grouping by day.
+pub type DateTime = chrono::DateTime<Utc>;
 
 /// Represents a clock in or out action.
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub enum Action {
-    In(DateTime),
-    Out(DateTime),
+    In(#[serde(deserialize_with = "deserialize_utc")] DateTime),
+    Out(#[serde(deserialize_with = "deserialize_utc")] DateTime),
+}
+
+/// Older timesheets were written with the local offset of wherever they were
+/// recorded, so accept any offset and normalize it to UTC.
+fn deserialize_utc<'de, D>(deserializer: D) -> Result<DateTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    chrono::DateTime::<FixedOffset>::deserialize(deserializer).map(|time| time.with_timezone(&Utc))
 }
 
 #[derive(Debug)]
@@ -19,6 +30,70 @@ pub enum This {
     Week,
     Month,
     Year,
+    /// Inclusive of both the start and end dates.
+    Range(NaiveDate, NaiveDate),
+}
+
+impl Action {
+    /// Returns when the action happened.
+    pub fn time(&self) -> &DateTime {
+        match self {
+            Action::In(time) => time,
+            Action::Out(time) => time,
+        }
+    }
+}
+
+/// How a session came to an end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum End {
+    /// Clocked out at the given time.
+    Out(DateTime),
+    /// The last clock in on the timesheet, still open.
+    Running,
+    /// Followed by another clock in, so it was never clocked out of.
+    Missing,
+}
+
+/// A clock in, paired with how it ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Session {
+    pub start: DateTime,
+    pub end: End,
+}
+
+impl Session {
+    /// Returns the length of the session, counting up to `now` if it's still
+    /// running, and at most `max_session`, if given. A session missing its clock
+    /// out has no length.
+    pub fn duration(&self, now: DateTime, max_session: Option<TimeDelta>) -> Option<TimeDelta> {
+        let end = match self.end {
+            End::Out(end) => end,
+            End::Running => now,
+            End::Missing => return None,
+        };
+
+        Some(cap_end(self.start, end, max_session).signed_duration_since(self.start))
+    }
+
+    /// Returns whether the session was clocked out of.
+    pub fn is_closed(&self) -> bool {
+        matches!(self.end, End::Out(_))
+    }
+}
+
+/// Version of the serialized timesheet written by this build.
+///
+/// - 0: versionless; timestamps carry the local offset they were recorded in.
+/// - 1: adds the `version` field; timestamps are written in UTC.
+pub const TIMESHEET_VERSION: u64 = 1;
+
+/// A clock in that was never clocked out of.
+#[derive(Debug, PartialEq)]
+pub struct Gap {
+    /// Position of the clock in within the timesheet.
+    pub index: usize,
+    pub clock_in: DateTime,
 }
 
 #[derive(Debug, Default, Deserialize, Serialize)]
@@ -26,6 +101,78 @@ pub struct Timesheet {
     pub clocks: VecDeque<Action>,
 }
 
+/// A timesheet stamped with the current schema version, for writing out.
+#[derive(Serialize)]
+pub struct Versioned<'a> {
+    version: u64,
+    #[serde(flatten)]
+    timesheet: &'a Timesheet,
+}
+
+/// Moves the end of a session earlier so that it lasts at most `max_session`, if
+/// given.
+fn cap_end(start: DateTime, end: DateTime, max_session: Option<TimeDelta>) -> DateTime {
+    max_session
+        .and_then(|max| start.checked_add_signed(max))
+        .map_or(end, |cap| end.min(cap))
+}
+
+/// Returns the first instant of a local calendar day. Where a DST change skips
+/// midnight, the day starts an hour later instead.
+fn start_of_day(day: NaiveDate) -> DateTime {
+    let midnight = day.and_time(NaiveTime::MIN);
+    let start = midnight.and_local_timezone(Local).earliest().or_else(|| {
+        (midnight + TimeDelta::try_hours(1).unwrap())
+            .and_local_timezone(Local)
+            .earliest()
+    });
+
+    start.map_or(midnight.and_utc(), |start| start.with_timezone(&Utc))
+}
+
+/// Returns the local calendar date of a timestamp.
+fn local_date(time: &DateTime) -> NaiveDate {
+    time.with_timezone(&Local).date_naive()
+}
+
+/// Pairs each clock in with the clock out that follows it. A clock in followed
+/// by another clock in is missing its clock out, and one followed by nothing is
+/// still running.
+fn pair_sessions<'a>(clocks: impl IntoIterator<Item = &'a Action>) -> Vec<Session> {
+    let mut sessions = Vec::new();
+    let mut last_clock_in = None;
+
+    for action in clocks {
+        match action {
+            Action::In(time) => {
+                if let Some(start) = last_clock_in.replace(*time) {
+                    sessions.push(Session {
+                        start,
+                        end: End::Missing,
+                    });
+                }
+            }
+            Action::Out(time) => {
+                if let Some(start) = last_clock_in.take() {
+                    sessions.push(Session {
+                        start,
+                        end: End::Out(*time),
+                    });
+                }
+            }
+        }
+    }
+
+    if let Some(start) = last_clock_in {
+        sessions.push(Session {
+            start,
+            end: End::Running,
+        });
+    }
+
+    sessions
+}
+
 fn closest_prev_monday(date: NaiveDate) -> NaiveDate {
     let days_so_far = date.weekday().num_days_from_monday();
     date.checked_sub_days(Days::new(days_so_far as u64))
@@ -33,19 +180,110 @@ fn closest_prev_monday(date: NaiveDate) -> NaiveDate {
 }
 
 impl Timesheet {
-    /// Returns the total time worked in hours.
-    pub fn total_time(&self, worked: &This) -> TimeDelta {
+    /// Stamps the timesheet with the current schema version.
+    pub fn versioned(&self) -> Versioned<'_> {
+        Versioned {
+            version: TIMESHEET_VERSION,
+            timesheet: self,
+        }
+    }
+
+    /// Returns the total time worked in hours. Each session counts for at most
+    /// `max_session`, if given.
+    pub fn total_time(&self, worked: &This, max_session: Option<TimeDelta>) -> TimeDelta {
         let mut total_time = TimeDelta::zero();
+        let mut last_clock_in = None;
 
+        for action in self.clocks_during(worked) {
+            match action {
+                Action::In(time) => {
+                    last_clock_in = Some(time);
+                }
+                Action::Out(time) => {
+                    if let Some(last_clock_in) = last_clock_in {
+                        let session = time.signed_duration_since(*last_clock_in);
+                        total_time += max_session.map_or(session, |max| session.min(max));
+                    }
+                }
+            }
+        }
+
+        total_time
+    }
+
+    /// Returns the average time worked per day, counting only the days in the
+    /// period with at least one completed session.
+    pub fn average_per_day(&self, worked: &This, max_session: Option<TimeDelta>) -> TimeDelta {
+        let days_worked = pair_sessions(self.clocks_during(worked))
+            .iter()
+            .filter(|session| session.is_closed())
+            .map(|session| local_date(&session.start))
+            .collect::<HashSet<_>>()
+            .len();
+
+        if days_worked == 0 {
+            return TimeDelta::zero();
+        }
+
+        self.total_time(worked, max_session) / days_worked as i32
+    }
+
+    /// Returns the time worked in each ISO week of a month. Weeks that straddle
+    /// the start or end of the month only count the days within it, and a
+    /// session spanning a boundary counts in each period for the time it spent
+    /// there.
+    pub fn monthly_report(
+        &self,
+        year: i32,
+        month: u32,
+        max_session: Option<TimeDelta>,
+    ) -> Vec<(IsoWeek, TimeDelta)> {
+        let Some(first) = NaiveDate::from_ymd_opt(year, month, 1) else {
+            return Vec::new();
+        };
+
+        let mut weeks: Vec<(IsoWeek, NaiveDate, NaiveDate)> = Vec::new();
+        for day in first.iter_days().take_while(|day| day.month() == month) {
+            match weeks.last_mut() {
+                Some((week, _, last)) if *week == day.iso_week() => *last = day,
+                _ => weeks.push((day.iso_week(), day, day)),
+            }
+        }
+
+        let sessions = self
+            .sessions()
+            .into_iter()
+            .filter_map(|session| match session.end {
+                End::Out(end) => Some((session.start, cap_end(session.start, end, max_session))),
+                End::Running | End::Missing => None,
+            })
+            .collect::<Vec<_>>();
+
+        weeks
+            .into_iter()
+            .map(|(week, from, to)| {
+                let from = start_of_day(from);
+                let to = start_of_day(to.succ_opt().unwrap());
+                let time_worked = sessions
+                    .iter()
+                    .map(|(start, end)| (*end.min(&to) - *start.max(&from)).max(TimeDelta::zero()))
+                    .sum();
+                (week, time_worked)
+            })
+            .collect()
+    }
+
+    /// Returns the actions that happened during the period.
+    fn clocks_during(&self, worked: &This) -> Vec<&Action> {
         let today = Local::now();
-        let mut last_clock_in = None;
-        let clocks: Vec<&Action> = match worked {
+
+        match worked {
             This::Day => self
                 .clocks
                 .iter()
                 .filter(|action| match action {
-                    Action::In(time) => time.date_naive() == today.date_naive(),
-                    Action::Out(time) => time.date_naive() == today.date_naive(),
+                    Action::In(time) => local_date(time) == today.date_naive(),
+                    Action::Out(time) => local_date(time) == today.date_naive(),
                 })
                 .collect(),
 
@@ -54,8 +292,8 @@ impl Timesheet {
                 self.clocks
                     .iter()
                     .filter(|action| match action {
-                        Action::In(time) => time.date_naive() >= monday,
-                        Action::Out(time) => time.date_naive() >= monday,
+                        Action::In(time) => local_date(time) >= monday,
+                        Action::Out(time) => local_date(time) >= monday,
                     })
                     .collect()
             }
@@ -65,8 +303,8 @@ impl Timesheet {
                 self.clocks
                     .iter()
                     .filter(|action| match action {
-                        Action::In(time) => time.date_naive() >= start_of_month,
-                        Action::Out(time) => time.date_naive() >= start_of_month,
+                        Action::In(time) => local_date(time) >= start_of_month,
+                        Action::Out(time) => local_date(time) >= start_of_month,
                     })
                     .collect()
             }
@@ -82,79 +320,167 @@ impl Timesheet {
                 self.clocks
                     .iter()
                     .filter(|action| match action {
-                        Action::In(time) => time.date_naive() >= start_of_year,
-                        Action::Out(time) => time.date_naive() >= start_of_year,
+                        Action::In(time) => local_date(time) >= start_of_year,
+                        Action::Out(time) => local_date(time) >= start_of_year,
                     })
                     .collect()
             }
-        };
 
-        for action in clocks {
-            match action {
-                Action::In(time) => {
-                    last_clock_in = Some(time);
-                }
-                Action::Out(time) => {
-                    if let Some(last_clock_in) = last_clock_in {
-                        total_time += time.signed_duration_since(*last_clock_in);
-                    }
-                }
+            This::Range(from, to) => self
+                .clocks
+                .iter()
+                .filter(|action| match action {
+                    Action::In(time) => (*from..=*to).contains(&local_date(time)),
+                    Action::Out(time) => (*from..=*to).contains(&local_date(time)),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the time worked on each day of the week containing `on`, Monday
+    /// first. A session still open counts from midnight up to now in today's
+    /// slot; days without work are zero. Each session counts for at most
+    /// `max_session`, if given.
+    pub fn weekly_hours(
+        &self,
+        on: Option<NaiveDate>,
+        max_session: Option<TimeDelta>,
+    ) -> [TimeDelta; 7] {
+        self.weekly_hours_at(on, Utc::now(), max_session)
+    }
+
+    fn weekly_hours_at(
+        &self,
+        on: Option<NaiveDate>,
+        now: DateTime,
+        max_session: Option<TimeDelta>,
+    ) -> [TimeDelta; 7] {
+        let today = local_date(&now);
+        let mut weekly_hours = [TimeDelta::zero(); 7];
+
+        for (hours, (day, sessions)) in weekly_hours
+            .iter_mut()
+            .zip(self.weekly_sessions(Some(on.unwrap_or(today))))
+        {
+            *hours = sessions
+                .iter()
+                .filter(|session| session.is_closed())
+                .filter_map(|session| session.duration(now, max_session))
+                .sum();
+
+            if day == today {
+                *hours += self.running_since_midnight(now, max_session);
             }
         }
 
-        total_time
+        weekly_hours
     }
 
-    pub fn weekly_hours(&self, on: Option<chrono::NaiveDate>) -> Vec<String> {
+    /// Returns the sessions started on each day of the week containing `on`,
+    /// Monday first.
+    pub fn weekly_sessions(&self, on: Option<NaiveDate>) -> Vec<(NaiveDate, Vec<Session>)> {
         let today = on.unwrap_or(Local::now().date_naive());
-        let one_day = Days::new(1);
-
         let monday = closest_prev_monday(today);
-        let tuesday = monday.checked_add_days(one_day).unwrap();
-        let wednesday = tuesday.checked_add_days(one_day).unwrap();
-        let thursday = wednesday.checked_add_days(one_day).unwrap();
-        let friday = thursday.checked_add_days(one_day).unwrap();
-        let saturday = friday.checked_add_days(one_day).unwrap();
-        let sunday = saturday.checked_add_days(one_day).unwrap();
-
-        let days = vec![
-            monday, tuesday, wednesday, thursday, friday, saturday, sunday,
-        ];
-
-        let none: Option<&chrono::DateTime<Local>> = None;
-
-        days.iter()
-            .map(|day| {
-                let (time_worked, _) = self
-                    .clocks
+        let sessions = self.sessions();
+
+        (0..7)
+            .map(|offset| {
+                let day = monday.checked_add_days(Days::new(offset)).unwrap();
+                let sessions = sessions
                     .iter()
-                    .filter(|clock| match &clock {
-                        Action::In(time) => time.date_naive() == *day,
-                        Action::Out(time) => time.date_naive() == *day,
-                    })
-                    .fold(
-                        (TimeDelta::zero(), none),
-                        |(mut time_worked, mut last_clock_in), clock| {
-                            match clock {
-                                Action::In(time) => last_clock_in = Some(time),
-                                Action::Out(time) => {
-                                    time_worked +=
-                                        time.signed_duration_since(last_clock_in.unwrap())
-                                }
-                            }
-                            (time_worked, last_clock_in)
-                        },
-                    );
-
-                format!(
-                    "{:02}:{:02}",
-                    time_worked.num_hours(),
-                    time_worked.num_minutes() % 60
-                )
+                    .filter(|session| local_date(&session.start) == day)
+                    .copied()
+                    .collect();
+
+                (day, sessions)
             })
             .collect()
     }
 
+    /// Pairs each clock in with the clock out that follows it.
+    pub fn sessions(&self) -> Vec<Session> {
+        pair_sessions(&self.clocks)
+    }
+
+    /// Finds clock ins that are missing a clock out: either followed directly by
+    /// another clock in, or left open since before today.
+    pub fn gaps(&self) -> Vec<Gap> {
+        let today = Local::now().date_naive();
+        let mut clocks = self.clocks.iter().enumerate().peekable();
+        let mut gaps = Vec::new();
+
+        while let Some((index, action)) = clocks.next() {
+            if let Action::In(clock_in) = action {
+                let missing_out = match clocks.peek() {
+                    Some((_, Action::In(_))) => true,
+                    Some((_, Action::Out(_))) => false,
+                    None => local_date(clock_in) < today,
+                };
+
+                if missing_out {
+                    gaps.push(Gap {
+                        index,
+                        clock_in: *clock_in,
+                    });
+                }
+            }
+        }
+
+        gaps
+    }
+
+    /// Returns the current and longest runs of consecutive days worked.
+    ///
+    /// A day is worked if a session started on it was clocked out of. Today
+    /// also counts while clocked in since yesterday or today, but not for a
+    /// clock in left open longer, which `gaps` reports instead. Since today may
+    /// not have been worked yet, a current streak that ended yesterday is still
+    /// going.
+    pub fn streaks(&self) -> (usize, usize) {
+        let today = Local::now().date_naive();
+        let mut days_worked = self
+            .sessions()
+            .iter()
+            .filter(|session| session.is_closed())
+            .map(|session| local_date(&session.start))
+            .collect::<BTreeSet<_>>();
+
+        if let Some(Action::In(clock_in)) = self.last_action() {
+            if local_date(clock_in) >= today.pred_opt().unwrap_or(today) {
+                days_worked.insert(today);
+            }
+        }
+
+        let mut streak = 0;
+        let mut longest = 0;
+        let mut last_day: Option<NaiveDate> = None;
+
+        for day in days_worked {
+            streak = match last_day {
+                Some(last_day) if last_day.succ_opt() == Some(day) => streak + 1,
+                _ => 1,
+            };
+            longest = longest.max(streak);
+            last_day = Some(day);
+        }
+
+        let current = match last_day {
+            Some(last_day) if last_day >= today.pred_opt().unwrap_or(today) => streak,
+            _ => 0,
+        };
+
+        (current, longest)
+    }
+
+    /// Sorts the clocks by time. Actions at the same instant keep their order.
+    ///
+    /// This doesn't check that clock ins and outs alternate.
+    pub fn canonicalize(&mut self) {
+        self.clocks
+            .make_contiguous()
+            .sort_by_key(|action| *action.time());
+    }
+
     // Clocks in.
     pub fn clock_in(&mut self, when: DateTime) {
         self.clocks.push_back(Action::In(when));
@@ -170,54 +496,58 @@ impl Timesheet {
         self.clocks.back()
     }
 
-    /// Returns the amount of time worked since clocking in today.
-    /// TODO: This is a bit of a mess, needs to be refactored.
-    pub fn running_time(&self) -> Option<TimeDelta> {
-        let now = Local::now();
-        let today = now.date_naive();
+    /// Returns the amount of time worked today, including the running session.
+    /// A session left open since before midnight only counts from midnight.
+    /// Each session counts for at most `max_session`, if given.
+    pub fn running_time(&self, max_session: Option<TimeDelta>) -> Option<TimeDelta> {
+        let now = Utc::now();
+        let today = local_date(&now);
 
-        let clocks = self
-            .clocks
+        let total_time: TimeDelta = self
+            .sessions()
             .iter()
-            .filter(|&clock| match clock {
-                Action::In(time) => time.date_naive() == today,
-                Action::Out(time) => time.date_naive() == today,
-            })
-            .collect::<Vec<_>>();
+            .filter(|session| session.is_closed() && local_date(&session.start) == today)
+            .filter_map(|session| session.duration(now, max_session))
+            .sum();
 
-        let mut last_clock_in = None;
-        let mut total_time = TimeDelta::zero();
+        Some(total_time + self.running_since_midnight(now, max_session))
+    }
 
-        for action in clocks {
-            match action {
-                Action::In(time) => {
-                    last_clock_in = Some(time);
-                }
-                Action::Out(time) => {
-                    if let Some(clock) = last_clock_in {
-                        total_time += time.signed_duration_since(*clock);
-                        last_clock_in = None;
-                    }
-                }
-            }
-        }
+    /// Returns how long the running session has been open as of `now`, counting
+    /// only from the local midnight of that day, and up to `max_session` after
+    /// it started, if given.
+    fn running_since_midnight(&self, now: DateTime, max_session: Option<TimeDelta>) -> TimeDelta {
+        let Some(Action::In(clock_in)) = self.last_action() else {
+            return TimeDelta::zero();
+        };
 
-        if let Some(last_clock_in) = last_clock_in {
-            Some(total_time + now.signed_duration_since(*last_clock_in))
-        } else {
-            Some(total_time)
-        }
+        let start = start_of_day(local_date(&now)).max(*clock_in);
+        let end = cap_end(*clock_in, now, max_session);
+
+        end.signed_duration_since(start).max(TimeDelta::zero())
     }
 }
 
 #[cfg(test)]
 mod timesheet_tests {
     use super::*;
+    use chrono::TimeZone;
+
+    /// Returns the given hour of a day in the local timezone.
+    fn local(year: i32, month: u32, day: u32, hour: u32) -> DateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
 
     #[test]
     fn total_time_today() {
         let mut timesheet = Timesheet::default();
-        let now = Local::now();
+        let now = Utc::now();
         let clock_in = now
             .checked_sub_signed(TimeDelta::try_hours(8).unwrap())
             .unwrap();
@@ -226,14 +556,14 @@ mod timesheet_tests {
         timesheet.clocks.push_back(Action::In(clock_in));
         timesheet.clocks.push_back(Action::Out(clock_out));
 
-        let total_time = timesheet.total_time(&This::Day);
+        let total_time = timesheet.total_time(&This::Day, None);
         assert_eq!(total_time.num_hours(), 8);
     }
 
     #[test]
     fn total_time_this_week() {
         let mut timesheet = Timesheet::default();
-        let now = Local::now();
+        let now = Utc::now();
         let clock_in = now
             .checked_sub_signed(TimeDelta::try_hours(8).unwrap())
             .unwrap();
@@ -242,14 +572,14 @@ mod timesheet_tests {
         timesheet.clock_in(clock_in);
         timesheet.clock_out(clock_out);
 
-        let total_time = timesheet.total_time(&This::Week);
+        let total_time = timesheet.total_time(&This::Week, None);
         assert_eq!(total_time.num_hours(), 8);
     }
 
     #[test]
     fn total_time_this_month() {
         let mut timesheet = Timesheet::default();
-        let now = Local::now();
+        let now = Utc::now();
         let clock_in = now
             .checked_sub_signed(TimeDelta::try_hours(8).unwrap())
             .unwrap();
@@ -258,14 +588,14 @@ mod timesheet_tests {
         timesheet.clock_in(clock_in);
         timesheet.clock_out(clock_out);
 
-        let total_time = timesheet.total_time(&This::Month);
+        let total_time = timesheet.total_time(&This::Month, None);
         assert_eq!(total_time.num_hours(), 8);
     }
 
     #[test]
     fn total_time_this_year() {
         let mut timesheet = Timesheet::default();
-        let now = Local::now();
+        let now = Utc::now();
         let clock_in = now
             .checked_sub_signed(TimeDelta::try_hours(8).unwrap())
             .unwrap();
@@ -274,14 +604,345 @@ mod timesheet_tests {
         timesheet.clock_in(clock_in);
         timesheet.clock_out(clock_out);
 
-        let total_time = timesheet.total_time(&This::Year);
+        let total_time = timesheet.total_time(&This::Year, None);
+        assert_eq!(total_time.num_hours(), 8);
+    }
+
+    #[test]
+    fn total_time_in_range() {
+        let mut timesheet = Timesheet::default();
+
+        // Before the range.
+        timesheet.clock_in(local(2024, 3, 9, 9));
+        timesheet.clock_out(local(2024, 3, 9, 17));
+        // On the first day of the range.
+        timesheet.clock_in(local(2024, 3, 10, 9));
+        timesheet.clock_out(local(2024, 3, 10, 12));
+        // On the last day of the range.
+        timesheet.clock_in(local(2024, 3, 20, 13));
+        timesheet.clock_out(local(2024, 3, 20, 18));
+        // After the range.
+        timesheet.clock_in(local(2024, 3, 21, 9));
+        timesheet.clock_out(local(2024, 3, 21, 17));
+
+        let from = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 3, 20).unwrap();
+        let total_time = timesheet.total_time(&This::Range(from, to), None);
         assert_eq!(total_time.num_hours(), 8);
     }
 
+    #[test]
+    fn running_time_since_yesterday() {
+        let mut timesheet = Timesheet::default();
+        let yesterday = Local::now().date_naive().pred_opt().unwrap();
+        let clock_in = local(yesterday.year(), yesterday.month(), yesterday.day(), 23);
+
+        timesheet.clock_in(clock_in);
+
+        let running_time = timesheet.running_time(None).unwrap();
+        assert!(running_time > TimeDelta::zero());
+        assert!(running_time < Utc::now().signed_duration_since(clock_in));
+    }
+
+    #[test]
+    fn gap_in_the_middle() {
+        let mut timesheet = Timesheet::default();
+        let now = Utc::now();
+        let hours_ago = |hours| {
+            now.checked_sub_signed(TimeDelta::try_hours(hours).unwrap())
+                .unwrap()
+        };
+
+        timesheet.clock_in(hours_ago(50));
+        timesheet.clock_out(hours_ago(49));
+        timesheet.clock_in(hours_ago(48));
+        timesheet.clock_in(hours_ago(26));
+        timesheet.clock_out(hours_ago(25));
+
+        assert_eq!(
+            timesheet.gaps(),
+            vec![Gap {
+                index: 2,
+                clock_in: hours_ago(48),
+            }]
+        );
+    }
+
+    #[test]
+    fn gap_left_open_before_today() {
+        let mut timesheet = Timesheet::default();
+        let yesterday = Local::now().date_naive().pred_opt().unwrap();
+        let clock_in = local(yesterday.year(), yesterday.month(), yesterday.day(), 9);
+
+        timesheet.clock_in(clock_in);
+
+        assert_eq!(timesheet.gaps(), vec![Gap { index: 0, clock_in }]);
+    }
+
+    #[test]
+    fn no_gap_while_clocked_in_today() {
+        let mut timesheet = Timesheet::default();
+
+        timesheet.clock_in(Utc::now());
+
+        assert!(timesheet.gaps().is_empty());
+    }
+
+    #[test]
+    fn same_instant_across_offsets() {
+        // Written by an older version while in UTC+09:00.
+        let timesheet: Timesheet = serde_json::from_str(
+            r#"{"clocks":[{"In":"2024-03-10T09:00:00+09:00"},{"Out":"2024-03-10T12:00:00+09:00"}]}"#,
+        )
+        .unwrap();
+
+        // The same instants, as observed from UTC-05:00.
+        let offset = FixedOffset::west_opt(5 * 3600).unwrap();
+        let clock_in = offset.with_ymd_and_hms(2024, 3, 9, 19, 0, 0).unwrap();
+        let clock_out = offset.with_ymd_and_hms(2024, 3, 9, 22, 0, 0).unwrap();
+
+        assert_eq!(
+            timesheet.clocks[0],
+            Action::In(clock_in.with_timezone(&Utc))
+        );
+        assert_eq!(
+            timesheet.clocks[1],
+            Action::Out(clock_out.with_timezone(&Utc))
+        );
+
+        let saved = serde_json::to_string(&timesheet).unwrap();
+        assert!(saved.contains("2024-03-10T00:00:00Z"));
+        assert!(saved.contains("2024-03-10T03:00:00Z"));
+    }
+
+    #[test]
+    fn total_time_with_max_session() {
+        let mut timesheet = Timesheet::default();
+        let max_session = TimeDelta::try_hours(9);
+
+        timesheet.clock_in(local(2024, 3, 11, 8));
+        timesheet.clock_out(local(2024, 3, 11, 18));
+        let monday = NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let total_time = timesheet.total_time(&This::Range(monday, monday), max_session);
+        assert_eq!(total_time.num_hours(), 9);
+
+        timesheet.clock_in(local(2024, 3, 12, 9));
+        timesheet.clock_out(local(2024, 3, 12, 17));
+        let tuesday = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let total_time = timesheet.total_time(&This::Range(tuesday, tuesday), max_session);
+        assert_eq!(total_time.num_hours(), 8);
+    }
+
+    #[test]
+    fn average_per_worked_day() {
+        let mut timesheet = Timesheet::default();
+
+        for day in [11, 13, 15] {
+            timesheet.clock_in(local(2024, 3, day, 9));
+            timesheet.clock_out(local(2024, 3, day, 17));
+        }
+
+        let from = NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 3, 17).unwrap();
+        let average = timesheet.average_per_day(&This::Range(from, to), None);
+
+        assert_eq!(average, TimeDelta::try_hours(8).unwrap());
+    }
+
+    #[test]
+    fn average_per_day_without_work() {
+        let timesheet = Timesheet::default();
+
+        assert_eq!(
+            timesheet.average_per_day(&This::Week, None),
+            TimeDelta::zero()
+        );
+    }
+
+    #[test]
+    fn monthly_report_by_week() {
+        let mut timesheet = Timesheet::default();
+
+        // Thursday of the week that March starts in.
+        timesheet.clock_in(local(2024, 2, 29, 9));
+        timesheet.clock_out(local(2024, 2, 29, 17));
+        // Friday, March 1st.
+        timesheet.clock_in(local(2024, 3, 1, 9));
+        timesheet.clock_out(local(2024, 3, 1, 17));
+        // Monday of the following week.
+        timesheet.clock_in(local(2024, 3, 4, 9));
+        timesheet.clock_out(local(2024, 3, 4, 13));
+        // Sunday, March 31st.
+        timesheet.clock_in(local(2024, 3, 31, 10));
+        timesheet.clock_out(local(2024, 3, 31, 12));
+
+        let report = timesheet
+            .monthly_report(2024, 3, None)
+            .into_iter()
+            .map(|(week, time_worked)| (week.week(), time_worked.num_hours()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(report, [(9, 8), (10, 4), (11, 0), (12, 0), (13, 2)]);
+    }
+
+    #[test]
+    fn monthly_report_splits_sessions_at_boundaries() {
+        let mut timesheet = Timesheet::default();
+
+        // Sunday night into Monday morning.
+        timesheet.clock_in(local(2024, 3, 10, 22));
+        timesheet.clock_out(local(2024, 3, 11, 2));
+        // Sunday, March 31st, into April.
+        timesheet.clock_in(local(2024, 3, 31, 22));
+        timesheet.clock_out(local(2024, 4, 1, 2));
+
+        let report = timesheet
+            .monthly_report(2024, 3, None)
+            .into_iter()
+            .map(|(week, time_worked)| (week.week(), time_worked.num_hours()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(report, [(9, 0), (10, 2), (11, 2), (12, 0), (13, 2)]);
+    }
+
+    #[test]
+    fn weekly_hours_by_weekday() {
+        let mut timesheet = Timesheet::default();
+
+        // Monday
+        timesheet.clock_in(local(2024, 3, 11, 9));
+        timesheet.clock_out(local(2024, 3, 11, 17));
+        // Wednesday, in two sessions.
+        timesheet.clock_in(local(2024, 3, 13, 9));
+        timesheet.clock_out(local(2024, 3, 13, 12));
+        timesheet.clock_in(local(2024, 3, 13, 13));
+        timesheet.clock_out(local(2024, 3, 13, 15));
+        // Sunday
+        timesheet.clock_in(local(2024, 3, 17, 10));
+        timesheet.clock_out(local(2024, 3, 17, 11));
+        // The following Monday, outside the week.
+        timesheet.clock_in(local(2024, 3, 18, 9));
+        timesheet.clock_out(local(2024, 3, 18, 17));
+
+        let on = NaiveDate::from_ymd_opt(2024, 3, 14);
+        let hours = timesheet
+            .weekly_hours(on, None)
+            .map(|time_worked| time_worked.num_hours());
+
+        assert_eq!(hours, [8, 0, 5, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn weekly_hours_includes_running_session() {
+        let mut timesheet = Timesheet::default();
+
+        timesheet.clock_in(local(2024, 3, 11, 9));
+        timesheet.clock_out(local(2024, 3, 11, 12));
+        timesheet.clock_in(local(2024, 3, 12, 22));
+
+        let hours = timesheet.weekly_hours_at(None, local(2024, 3, 13, 3), None);
+
+        let expected = [3, 0, 3, 0, 0, 0, 0].map(|hours| TimeDelta::try_hours(hours).unwrap());
+        assert_eq!(hours, expected);
+    }
+
+    #[test]
+    fn weekly_hours_with_max_session() {
+        let mut timesheet = Timesheet::default();
+
+        timesheet.clock_in(local(2024, 3, 11, 9));
+        timesheet.clock_out(local(2024, 3, 11, 21));
+        timesheet.clock_in(local(2024, 3, 13, 8));
+
+        let hours =
+            timesheet.weekly_hours_at(None, local(2024, 3, 13, 20), TimeDelta::try_hours(9));
+
+        let expected = [9, 0, 9, 0, 0, 0, 0].map(|hours| TimeDelta::try_hours(hours).unwrap());
+        assert_eq!(hours, expected);
+    }
+
+    fn work_days_ago(timesheet: &mut Timesheet, days_ago: u64) {
+        let day = Local::now()
+            .date_naive()
+            .checked_sub_days(Days::new(days_ago))
+            .unwrap();
+
+        timesheet.clock_in(local(day.year(), day.month(), day.day(), 9));
+        timesheet.clock_out(local(day.year(), day.month(), day.day(), 10));
+    }
+
+    #[test]
+    fn streak_reset_by_gap() {
+        let mut timesheet = Timesheet::default();
+
+        for days_ago in [7, 6, 5, 3, 2, 1] {
+            work_days_ago(&mut timesheet, days_ago);
+        }
+        assert_eq!(timesheet.streaks(), (3, 3));
+
+        work_days_ago(&mut timesheet, 0);
+        assert_eq!(timesheet.streaks(), (4, 4));
+    }
+
+    #[test]
+    fn streak_broken_before_yesterday() {
+        let mut timesheet = Timesheet::default();
+
+        for days_ago in [4, 3, 2] {
+            work_days_ago(&mut timesheet, days_ago);
+        }
+
+        assert_eq!(timesheet.streaks(), (0, 3));
+    }
+
+    #[test]
+    fn streak_counts_today_while_clocked_in() {
+        let mut timesheet = Timesheet::default();
+
+        work_days_ago(&mut timesheet, 1);
+        timesheet.clock_in(Utc::now());
+
+        assert_eq!(timesheet.streaks(), (2, 2));
+    }
+
+    #[test]
+    fn streak_ignores_stale_clock_in() {
+        let mut timesheet = Timesheet::default();
+
+        work_days_ago(&mut timesheet, 6);
+        work_days_ago(&mut timesheet, 5);
+        let stale = Local::now()
+            .date_naive()
+            .checked_sub_days(Days::new(4))
+            .unwrap();
+        timesheet.clock_in(local(stale.year(), stale.month(), stale.day(), 9));
+
+        assert_eq!(timesheet.streaks(), (0, 2));
+    }
+
+    #[test]
+    fn canonicalize_is_stable() {
+        let mut timesheet = Timesheet::default();
+        let now = Utc::now();
+        let earlier = now
+            .checked_sub_signed(TimeDelta::try_hours(1).unwrap())
+            .unwrap();
+
+        timesheet.clock_out(now);
+        timesheet.clock_in(now);
+        timesheet.clock_in(earlier);
+        timesheet.canonicalize();
+
+        assert_eq!(
+            timesheet.clocks,
+            [Action::In(earlier), Action::Out(now), Action::In(now)]
+        );
+    }
+
     #[test]
     fn last_action() {
         let mut timesheet = Timesheet::default();
-        let now = Local::now();
+        let now = Utc::now();
         let clock_in = now
             .checked_sub_signed(TimeDelta::try_hours(8).unwrap())
             .unwrap();
@@ -296,14 +957,14 @@ mod timesheet_tests {
     #[test]
     fn running_time() {
         let mut timesheet = Timesheet::default();
-        let now = Local::now();
+        let now = Utc::now();
         let clock_in = now
             .checked_sub_signed(TimeDelta::try_hours(8).unwrap())
             .unwrap();
 
         timesheet.clock_in(clock_in);
 
-        let running_time = timesheet.running_time().unwrap();
+        let running_time = timesheet.running_time(None).unwrap();
         assert_eq!(
             running_time
                 .checked_sub(&TimeDelta::nanoseconds(running_time.subsec_nanos() as i64))